@@ -0,0 +1,254 @@
+/*!
+ * Reproducible crawl benchmarks.
+ *
+ * Drives the real listing and download engine (`list_issues`/`run_downloads`)
+ * against one or more workloads described by a JSON file, measuring wall
+ * time, throughput, and per-request latency percentiles for each.  The
+ * resulting report also captures the environment it ran in (hostname, CPU
+ * count, OS, build identity) and can optionally be POSTed to a results
+ * server, so that regressions in the download engine or storage backend
+ * show up as a throughput change across commits rather than going
+ * unnoticed.
+ */
+
+use crate::BugcrawlError;
+use crate::Bugcrawl;
+use crate::FilesystemStore;
+use reqwest::Client;
+use serde::Deserialize;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+/**
+ * One crawl configuration to benchmark, as described in a workload file.
+ */
+#[derive(Deserialize)]
+pub struct Workload {
+    /** human-readable label for this workload, used in the report */
+    pub name: String,
+    /** `sort` value passed to the list endpoint: "created", "updated", or "key" */
+    pub sort: String,
+    /** number of issue downloads to run concurrently */
+    pub concurrency: usize,
+    /** minimum delay in milliseconds between issue-download requests */
+    pub request_delay_ms: u64,
+    /** stop after downloading this many issues */
+    pub max_issues: usize,
+}
+
+/**
+ * BenchConfig describes a full benchmark run: one or more workloads, and
+ * optionally where to report the resulting `BenchReport`.
+ */
+#[derive(Deserialize)]
+pub struct BenchConfig {
+    pub workloads: Vec<Workload>,
+    /** URL to POST the resulting `BenchReport` to as JSON, if set */
+    pub results_server: Option<String>,
+}
+
+/**
+ * Information about the machine and build a benchmark ran on.
+ */
+#[derive(Serialize)]
+pub struct EnvironmentInfo {
+    pub hostname: String,
+    pub cpu_count: usize,
+    pub os: String,
+    pub crate_version: String,
+    /**
+     * `git describe --always --dirty` run in the working directory the
+     * benchmark is started from, if that succeeds; this is not necessarily
+     * the checkout the binary was built from
+     */
+    pub git_describe: Option<String>,
+}
+
+/**
+ * Results of running one workload to completion.
+ */
+#[derive(Serialize)]
+pub struct WorkloadResult {
+    pub name: String,
+    pub issues_fetched: usize,
+    pub wall_time_ms: u64,
+    pub issues_per_sec: f64,
+    pub latency_p50_ms: f64,
+    pub latency_p95_ms: f64,
+    pub latency_p99_ms: f64,
+    pub bytes_transferred: u64,
+}
+
+/** A full benchmark report: environment plus one result per workload. */
+#[derive(Serialize)]
+pub struct BenchReport {
+    pub environment: EnvironmentInfo,
+    pub results: Vec<WorkloadResult>,
+}
+
+/**
+ * Run every workload in `config` in order, returning the resulting report.
+ * If `config.results_server` is set, the report is also POSTed there as
+ * JSON before being returned.
+ */
+pub fn run_benchmark(config: &BenchConfig) -> Result<BenchReport, BugcrawlError> {
+    let environment = gather_environment();
+    let mut results = Vec::new();
+
+    for workload in &config.workloads {
+        tracing::info!(workload = workload.name.as_str(), "starting benchmark workload");
+        let result = run_workload(workload)?;
+        tracing::info!(
+            workload = workload.name.as_str(),
+            issues_per_sec = result.issues_per_sec,
+            "finished benchmark workload"
+        );
+        results.push(result);
+    }
+
+    let report = BenchReport { environment, results };
+
+    if let Some(url) = &config.results_server {
+        post_report(url, &report)?;
+    }
+
+    Ok(report)
+}
+
+/** Run a single workload against the real download engine, timing every issue. */
+fn run_workload(workload: &Workload) -> Result<WorkloadResult, BugcrawlError> {
+    let client = Client::builder()
+        .timeout(Duration::from_millis(crate::BUGVIEW_REQUEST_TIMEOUT))
+        .connect_timeout(Duration::from_millis(crate::BUGVIEW_CONNECT_TIMEOUT))
+        .user_agent(crate::BUGCRAWL_USER_AGENT)
+        .build()?;
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    let directory = std::env::temp_dir().join(format!("bugcrawl-bench-{}", workload.name));
+    let store: Arc<dyn crate::Store> = Arc::new(FilesystemStore::new(directory)?);
+
+    let mut bcp = Bugcrawl {
+        store,
+        bugview_client: client,
+        tokio_runtime: runtime,
+        concurrency: workload.concurrency,
+        log_completed_requests: false,
+    };
+
+    let items = crate::list_issues(&mut bcp, &workload.sort, None, Some(workload.max_issues))?;
+    let issue_ids: Vec<String> = items.into_iter().map(|item| item.key).collect();
+
+    let start = Instant::now();
+    let outcomes = crate::run_downloads(
+        &bcp,
+        issue_ids,
+        Duration::from_millis(workload.request_delay_ms),
+    )?;
+    let wall_time = start.elapsed();
+
+    let issues_fetched = outcomes.len();
+    let bytes_transferred: u64 = outcomes.iter().map(|o| o.bytes_written).sum();
+    let mut latencies_ms: Vec<f64> =
+        outcomes.iter().map(|o| o.elapsed.as_secs_f64() * 1000.0).collect();
+    latencies_ms.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    let wall_time_ms = wall_time.as_millis() as u64;
+    let issues_per_sec = if wall_time.as_secs_f64() > 0.0 {
+        issues_fetched as f64 / wall_time.as_secs_f64()
+    } else {
+        0.0
+    };
+
+    Ok(WorkloadResult {
+        name: workload.name.clone(),
+        issues_fetched,
+        wall_time_ms,
+        issues_per_sec,
+        latency_p50_ms: percentile(&latencies_ms, 0.50),
+        latency_p95_ms: percentile(&latencies_ms, 0.95),
+        latency_p99_ms: percentile(&latencies_ms, 0.99),
+        bytes_transferred,
+    })
+}
+
+/**
+ * Percentile of an already-sorted slice of millisecond latencies: the element
+ * at index `p * (len - 1)`, rounded to the nearest index.
+ */
+fn percentile(sorted_ms: &[f64], p: f64) -> f64 {
+    if sorted_ms.is_empty() {
+        return 0.0;
+    }
+    let rank = (p * (sorted_ms.len() as f64 - 1.0)).round() as usize;
+    sorted_ms[rank.min(sorted_ms.len() - 1)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentile_of_empty_slice_is_zero() {
+        assert_eq!(percentile(&[], 0.5), 0.0);
+    }
+
+    #[test]
+    fn percentile_picks_nearest_rank() {
+        let latencies = vec![10.0, 20.0, 30.0, 40.0, 50.0];
+        assert_eq!(percentile(&latencies, 0.0), 10.0);
+        assert_eq!(percentile(&latencies, 1.0), 50.0);
+        assert_eq!(percentile(&latencies, 0.5), 30.0);
+    }
+}
+
+/** Capture hostname, CPU count, OS, and build identity for the report. */
+fn gather_environment() -> EnvironmentInfo {
+    EnvironmentInfo {
+        hostname: hostname::get()
+            .ok()
+            .and_then(|h| h.into_string().ok())
+            .unwrap_or_else(|| "unknown".to_string()),
+        cpu_count: num_cpus::get(),
+        os: std::env::consts::OS.to_string(),
+        crate_version: env!("CARGO_PKG_VERSION").to_string(),
+        git_describe: git_describe(),
+    }
+}
+
+/**
+ * Best-effort `git describe`, run at benchmark time in the current working
+ * directory.  It does not record the checkout the binary was built from.
+ */
+fn git_describe() -> Option<String> {
+    std::process::Command::new("git")
+        .args(["describe", "--always", "--dirty"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|s| s.trim().to_string())
+}
+
+/** POST `report` as JSON to `url`, using a short-lived blocking client. */
+fn post_report(url: &str, report: &BenchReport) -> Result<(), BugcrawlError> {
+    let client = reqwest::blocking::Client::new();
+    let response = client.post(url).json(report).send().map_err(|e| BugcrawlError {
+        message: format!("failed to POST benchmark report to {}: {}", url, e),
+    })?;
+    if !response.status().is_success() {
+        return Err(BugcrawlError {
+            message: format!(
+                "results server at {} rejected benchmark report: {}",
+                url,
+                response.status()
+            ),
+        });
+    }
+    Ok(())
+}
@@ -10,15 +10,39 @@
  * The `sort` value can be `updated`, `created`, or `key`.
  */
 
+use async_compression::tokio::write::GzipEncoder;
+use futures::TryStreamExt;
 use reqwest::Client;
 use serde::Deserialize;
 use serde::Serialize;
-use std::fs;
+use std::collections::HashSet;
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+use tokio::sync::Semaphore;
+use tokio_util::io::StreamReader;
 
+mod bench;
 mod error;
+mod logging;
+mod metrics;
+mod ratelimit;
+mod state;
+mod store;
+pub use bench::run_benchmark;
+pub use bench::BenchConfig;
 pub use error::BugcrawlError;
+pub use logging::LogFormat;
+pub use logging::LoggingParams;
+pub use metrics::MetricsParams;
+use ratelimit::RateLimiter;
+use state::CrawlState;
+pub use store::FilesystemStore;
+pub use store::S3Store;
+pub use store::Store;
 
 /** "user-agent" header value for crawl requests */
 const BUGCRAWL_USER_AGENT: &str = "bugcrawl";
@@ -32,40 +56,72 @@ const BUGVIEW_DELAY_LIST: u64 = 500;
 const BUGVIEW_DELAY_GET_ISSUE: u64 = 1500;
 /** maximum allowed size for any issue's JSON blob */
 const MAX_ISSUE_LEN: usize = 10 * 1024 * 1024;
+/** default number of issue downloads to run concurrently */
+const DEFAULT_CONCURRENCY: usize = 4;
 
-/** print a debug message for each request */
-const DBG_REQ: bool = false;
-/* print a debug message for each issue downloaded */
-const DBG_ISSUE: bool = true;
+/**
+ * Selects which `Store` implementation a crawl writes issues into.
+ */
+pub enum StoreBackend<'a> {
+    /** flat files under a local directory (today's default behavior) */
+    Filesystem { directory: &'a str },
+    /** objects in an S3-compatible bucket, keyed by issue id */
+    S3 { bucket: &'a str, prefix: &'a str },
+}
+
+/**
+ * Selects how a crawl decides which issues to fetch.
+ */
+pub enum CrawlMode {
+    /** fetch every issue bugview has, skipping ones we already have */
+    Full,
+    /**
+     * only fetch issues bugview reports as updated after the watermark
+     * persisted by the last crawl (`Full` or `Update`), refreshing them even if we
+     * already have a (now-stale) local copy
+     */
+    Update,
+}
 
 /**
  * BugcrawlParams is used by consumers (i.e., `main()`) to describe what they
  * want to do.
  */
 pub struct BugcrawlParams<'a> {
-    /** local directory into which to store issue contents */
-    pub filepath: &'a str,
+    /** where to write downloaded issue contents */
+    pub store: StoreBackend<'a>,
+    /** number of issue downloads to run concurrently */
+    pub concurrency: usize,
+    /** optional Prometheus metrics exporter */
+    pub metrics: Option<MetricsParams>,
+    /** tracing subscriber configuration */
+    pub logging: LoggingParams,
+    /** whether to crawl every issue or only ones updated since last time */
+    pub mode: CrawlMode,
 }
 
 /**
  * Stores the runtime state of the Bugcrawl operation.
  */
 pub struct Bugcrawl {
-    /** path to the local directory of bug files */
-    filepath: PathBuf,
+    /** where we write downloaded issue contents */
+    store: Arc<dyn Store>,
     /** HTTP client for the bugview API */
     bugview_client: Client,
     /** tokio runtime */
     tokio_runtime: tokio::runtime::Runtime,
+    /** number of issue downloads to run concurrently */
+    concurrency: usize,
+    /** whether to emit a tracing event for every completed HTTP request */
+    log_completed_requests: bool,
 }
 
 /**
- * Crawl the "bugview" web service.  Currently, results are stored into flat
- * files in params.filepath.
+ * Crawl the "bugview" web service.  Results are written through whichever
+ * `Store` backend `params.store` selects.
  */
 pub fn bugcrawl(params: BugcrawlParams) -> Result<(), BugcrawlError> {
-    let mut filepath = PathBuf::new();
-    filepath.push(params.filepath);
+    logging::init(&params.logging)?;
 
     let client = Client::builder()
         .timeout(Duration::from_millis(BUGVIEW_REQUEST_TIMEOUT))
@@ -73,57 +129,134 @@ pub fn bugcrawl(params: BugcrawlParams) -> Result<(), BugcrawlError> {
         .user_agent(BUGCRAWL_USER_AGENT)
         .build()?;
 
-    let runtime = tokio::runtime::Builder::new()
-        .basic_scheduler()
+    let runtime = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()
         .unwrap();
 
+    let store = build_store(&params.store, runtime.handle().clone())?;
+    let concurrency = if params.concurrency > 0 { params.concurrency } else { DEFAULT_CONCURRENCY };
+
+    if let Some(metrics_params) = &params.metrics {
+        let _guard = runtime.enter();
+        metrics::install(metrics_params)?;
+    }
+
     let mut bcp = Bugcrawl {
-        filepath: filepath,
+        store: store,
         bugview_client: client,
         tokio_runtime: runtime,
+        concurrency: concurrency,
+        log_completed_requests: params.logging.log_completed_requests,
     };
 
-    init_directory(&bcp)?;
-    eprintln!("fetching full list of issue ids");
-    let issue_ids = list_issues(&mut bcp)?;
-    eprintln!("total issues: {}", issue_ids.len());
-    eprintln!("determining which issues we already have");
-    let new_issue_ids = issue_ids.iter().filter(|issue_id| {
-        let newpath = path_for_issue(&bcp, issue_id, false);
-        match std::fs::metadata(&newpath.as_path()) {
-            Err(e) if e.kind() == std::io::ErrorKind::NotFound => true,
-            Ok(_) => false,
-            // XXX Is there a way to propagate this cleanly?
-            Err(e) => panic!("failed to get local metadata for {}: {}",
-                newpath.as_path().display(), e)
-        }
-    }).collect::<Vec<&String>>();
-    eprintln!("total issues:       {}", issue_ids.len());
-    eprintln!("issues to download: {}", new_issue_ids.len());
+    let state = CrawlState::load(bcp.store.as_ref())?;
+    let seen_ids: HashSet<String> = state.high_watermark_ids.iter().cloned().collect();
+    let (sort_field, watermark) = match params.mode {
+        CrawlMode::Full => ("created", None),
+        CrawlMode::Update => (
+            "updated",
+            state.high_watermark.as_deref().map(|timestamp| Watermark {
+                timestamp,
+                seen_ids: &seen_ids,
+            }),
+        ),
+    };
+    let old_watermark_timestamp = state.high_watermark.clone();
 
-    let mut count = 0;
-    for issue_id in new_issue_ids.iter() {
-        count = count + 1;
+    tracing::info!(mode = sort_field, "fetching list of issue ids");
+    let items = list_issues(&mut bcp, sort_field, watermark, None)?;
+    tracing::info!(count = items.len(), "issues listed");
 
-        if DBG_ISSUE {
-            eprintln!("downloading: {}", issue_id);
+    // The new watermark timestamp is the most recent `updated` timestamp
+    // across everything we just listed (ISO 8601 timestamps sort lexically,
+    // so a plain string max works here), or the old one unchanged if
+    // nothing new came back.  The ids tracked alongside it are reset unless
+    // the timestamp didn't move, in which case newly-seen ids at that exact
+    // instant are merged into the old set rather than replacing it.
+    let new_watermark_timestamp = items
+        .iter()
+        .map(|item| item.updated.clone())
+        .max()
+        .or_else(|| old_watermark_timestamp.clone());
+    let mut new_watermark_ids: HashSet<String> =
+        if new_watermark_timestamp == old_watermark_timestamp {
+            seen_ids
+        } else {
+            HashSet::new()
+        };
+    for item in &items {
+        if Some(item.updated.as_str()) == new_watermark_timestamp.as_deref() {
+            new_watermark_ids.insert(item.key.clone());
         }
-        download_issue(&mut bcp, issue_id)?;
-        if count % 100 == 1 {
-            eprintln!("downloaded {} issues", count);
+    }
+
+    // Whether we need to know what's already in the store: always for a
+    // `Full` crawl, and for `Update` only when there's no watermark to rely
+    // on instead.  When we do, list everything up front in one round trip
+    // rather than probing `exists()` once per issue, which on a backend
+    // like `S3Store` would mean one network request per issue before a
+    // single download even starts.
+    let needs_existing_check = match params.mode {
+        CrawlMode::Full => true,
+        CrawlMode::Update => state.high_watermark.is_none(),
+    };
+    let existing: HashSet<String> = if needs_existing_check {
+        bcp.store.list_existing()?.into_iter().collect()
+    } else {
+        HashSet::new()
+    };
+
+    let mut new_issue_ids: Vec<String> = Vec::new();
+    for item in items.into_iter() {
+        let need_download = match params.mode {
+            CrawlMode::Full => !existing.contains(&item.key),
+            // A prior watermark means bugview already told us these items
+            // are new or changed since then, so we always re-fetch them.
+            // Without one (e.g. the first crawl into this store), we have
+            // no such guarantee, so fall back to only
+            // fetching what we don't already have, same as `Full`.
+            CrawlMode::Update if state.high_watermark.is_some() => true,
+            CrawlMode::Update => !existing.contains(&item.key),
+        };
+        if need_download {
+            new_issue_ids.push(item.key);
+        } else {
+            ::metrics::counter!("bugcrawl_issues_skipped_total", 1);
         }
     }
+    tracing::info!(count = new_issue_ids.len(), "issues to download");
+
+    run_downloads(&bcp, new_issue_ids, Duration::from_millis(BUGVIEW_DELAY_GET_ISSUE))?;
+
+    if let Some(high_watermark) = new_watermark_timestamp {
+        let mut state = state;
+        state.high_watermark = Some(high_watermark);
+        state.high_watermark_ids = new_watermark_ids.into_iter().collect();
+        state.save(bcp.store.as_ref())?;
+    }
 
     Ok(())
 }
 
 /**
- * Initialize the directory into which we will store downloaded issue files.
+ * Construct the `Store` selected by `backend`, handing `runtime_handle` to
+ * backends (e.g. `S3Store`) that need to drive async I/O.
  */
-pub fn init_directory(bcp: &Bugcrawl) -> Result<(), BugcrawlError> {
-    Ok(fs::create_dir_all(bcp.filepath.as_path())?)
+fn build_store(
+    backend: &StoreBackend,
+    runtime_handle: tokio::runtime::Handle,
+) -> Result<Arc<dyn Store>, BugcrawlError> {
+    match backend {
+        StoreBackend::Filesystem { directory } => {
+            let mut path = PathBuf::new();
+            path.push(directory);
+            Ok(Arc::new(FilesystemStore::new(path)?))
+        }
+        StoreBackend::S3 { bucket, prefix } => {
+            Ok(Arc::new(S3Store::new(bucket, prefix, runtime_handle)?))
+        }
+    }
 }
 
 /**
@@ -151,31 +284,82 @@ struct IssueListItem {
 }
 
 /**
- * List all of the issues in bugview, returning a list of the identifiers.
+ * A cursor marking how far a previous crawl got: the most recent `updated`
+ * timestamp seen, plus the ids of every issue already fetched at exactly
+ * that timestamp.  The id set disambiguates "already seen at this instant"
+ * from "updated again at this instant" for issues that tie on `updated`.
  */
-fn list_issues(mut bcp: &mut Bugcrawl) -> Result<Vec<String>, BugcrawlError> {
+struct Watermark<'a> {
+    timestamp: &'a str,
+    seen_ids: &'a HashSet<String>,
+}
+
+impl<'a> Watermark<'a> {
+    /** Whether an issue with this `updated` timestamp is older than the watermark. */
+    fn excludes(&self, updated: &str) -> bool {
+        updated < self.timestamp
+    }
+
+    /** Whether an issue tied with the watermark's timestamp was already fetched. */
+    fn already_seen(&self, updated: &str, id: &str) -> bool {
+        updated == self.timestamp && self.seen_ids.contains(id)
+    }
+}
+
+/**
+ * List issues in bugview sorted by `sort_field`, returning the matching
+ * summary items.  If `watermark` is set, paging stops as soon as an issue is
+ * reached whose `updated` timestamp is strictly older than the watermark's —
+ * this is how `CrawlMode::Update` avoids walking the entire bugview history,
+ * relying on `sort=updated` returning the most recently updated issues
+ * first.  Issues tied with the watermark's timestamp are included unless
+ * they're already in `watermark.seen_ids`.  If `limit` is set, paging also
+ * stops as soon as `limit` items have been collected, bounding how much
+ * listing work a caller that only wants the first few issues (e.g. a
+ * benchmark workload) has to pay for.
+ */
+fn list_issues(
+    mut bcp: &mut Bugcrawl,
+    sort_field: &str,
+    watermark: Option<Watermark>,
+    limit: Option<usize>,
+) -> Result<Vec<IssueListItem>, BugcrawlError>
+{
     let baseurl =
         reqwest::Url::parse("https://smartos.org/bugview/index.json?").unwrap();
     let mut offset: usize = 0;
-    let mut issue_ids: Vec<String> = Vec::new();
+    let mut items: Vec<IssueListItem> = Vec::new();
 
-    loop {
-        let page = list_issues_page(&mut bcp, &baseurl, "created", offset)?;
-        for item in page.issues.iter() {
-            issue_ids.push(item.key.clone());
+    'paging: loop {
+        let page = list_issues_page(&mut bcp, &baseurl, sort_field, offset)?;
+        let page_len = page.issues.len();
+        for item in page.issues.into_iter() {
+            if let Some(watermark) = &watermark {
+                if watermark.excludes(item.updated.as_str()) {
+                    break 'paging;
+                }
+                if watermark.already_seen(item.updated.as_str(), &item.key) {
+                    continue;
+                }
+            }
+            items.push(item);
+            if limit.map_or(false, |limit| items.len() >= limit) {
+                break 'paging;
+            }
         }
-        offset = page.offset + page.issues.len();
-        if page.offset + page.issues.len() >= page.total {
+        offset = page.offset + page_len;
+        if page.offset + page_len >= page.total {
             break;
         }
     }
 
-    Ok(issue_ids)
+    Ok(items)
 }
 
 /**
  * List one page worth of issues from bugview.
  */
+#[tracing::instrument(skip(bcp, baseurl))]
 fn list_issues_page(
     bcp: &mut Bugcrawl,
     baseurl: &reqwest::Url,
@@ -195,89 +379,212 @@ fn list_issues_page(
     };
     let client = &bcp.bugview_client;
     let request = client.get(url).query(&params).build()?;
-    let response = make_request(bcp, request)?;
+    let response = make_request(bcp, request, metrics::ENDPOINT_LIST)?;
     let runtime = &mut bcp.tokio_runtime;
     let page: IssueListPage = runtime.block_on(async {
         response.json().await
     })?;
-    eprintln!("listed {} of {} total issues", page.offset, page.total);
+    tracing::debug!(offset = page.offset, total = page.total, "listed page");
+    ::metrics::counter!("bugcrawl_issues_listed_total", page.issues.len() as u64);
     std::thread::sleep(Duration::from_millis(BUGVIEW_DELAY_LIST));
     Ok(page)
 }
 
 /**
- * Given an issue identifier, return the local filesystem path where we will
- * store the issue.  If `tmp` is set, return a temporary file name to be used
- * for this issue's content.
+ * What we learn from successfully downloading one issue: how long it took
+ * from sending the request until the issue was stored, and how many
+ * (compressed) bytes we wrote.  `run_downloads`
+ * collects these so that benchmarking (see `bench.rs`) can report latency
+ * percentiles and throughput without reimplementing the download engine.
  */
-fn path_for_issue(bcp: &Bugcrawl, issue_id: &str, tmp: bool)
-    -> std::path::PathBuf
-{
-    let mut newpath = std::path::PathBuf::new();
-    newpath.push(&bcp.filepath);
-    // XXX sanity-check for invalid characters
-    newpath.push(format!("{}.json{}", issue_id, if tmp { ".tmp" } else { "" }));
-    newpath
+struct DownloadOutcome {
+    elapsed: Duration,
+    bytes_written: u64,
 }
 
 /**
- * Download the contents of the specified issue to the corresponding local file.
+ * Download `issue_ids` with up to `bcp.concurrency` requests in flight at
+ * once (falling back to `DEFAULT_CONCURRENCY` if it's zero, since a
+ * zero-permit semaphore would never let any download proceed), governed by
+ * a rate limiter shared across all of them with a minimum spacing of
+ * `request_delay`.  Individual download failures don't abort the run:
+ * they're collected, and once every issue has been attempted, a summary
+ * error is returned if any failed; otherwise the per-issue outcomes are
+ * returned for the caller to summarize.
  */
-fn download_issue(mut bcp: &mut Bugcrawl, issue_id: &String)
-    -> Result<(), BugcrawlError>
-{
-    let client = &bcp.bugview_client;
+fn run_downloads(
+    bcp: &Bugcrawl,
+    issue_ids: Vec<String>,
+    request_delay: Duration,
+) -> Result<Vec<DownloadOutcome>, BugcrawlError> {
+    let total = issue_ids.len();
+    let store = Arc::clone(&bcp.store);
+    let client = bcp.bugview_client.clone();
+    let concurrency = if bcp.concurrency > 0 { bcp.concurrency } else { DEFAULT_CONCURRENCY };
+    let semaphore = Arc::new(Semaphore::new(concurrency));
+    let limiter = Arc::new(RateLimiter::new(request_delay));
+    let log_completed_requests = bcp.log_completed_requests;
+    let (tx, mut rx) = mpsc::unbounded_channel::<(String, Result<DownloadOutcome, BugcrawlError>)>();
+
+    bcp.tokio_runtime.block_on(async move {
+        for issue_id in issue_ids {
+            let store = Arc::clone(&store);
+            let client = client.clone();
+            let semaphore = Arc::clone(&semaphore);
+            let limiter = Arc::clone(&limiter);
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                let _permit = semaphore.acquire_owned().await.unwrap();
+                limiter.acquire().await;
+                tracing::debug!(issue_id = issue_id.as_str(), "downloading");
+                let result = download_one(&client, store, &issue_id, log_completed_requests).await;
+                let _ = tx.send((issue_id, result));
+            });
+        }
+        drop(tx);
+
+        let mut count = 0;
+        let mut outcomes: Vec<DownloadOutcome> = Vec::new();
+        let mut failures: Vec<(String, BugcrawlError)> = Vec::new();
+        while let Some((issue_id, result)) = rx.recv().await {
+            count = count + 1;
+            match result {
+                Ok(outcome) => {
+                    tracing::debug!(issue_id = issue_id.as_str(), "downloaded");
+                    outcomes.push(outcome);
+                }
+                Err(error) => {
+                    tracing::warn!(issue_id = issue_id.as_str(), %error, "failed to download issue");
+                    failures.push((issue_id, error));
+                }
+            }
+            if count % 100 == 0 {
+                tracing::info!(count, total, "progress");
+            }
+        }
+
+        if failures.is_empty() {
+            Ok(outcomes)
+        } else {
+            let (first_id, first_error) = &failures[0];
+            Err(BugcrawlError {
+                message: format!(
+                    "{} of {} issues failed to download (first failure, {}: {})",
+                    failures.len(), total, first_id, first_error
+                ),
+            })
+        }
+    })
+}
+
+/**
+ * Download and store a single issue.  This is the unit of work spawned onto
+ * the concurrent download engine in `run_downloads`.
+ */
+async fn download_one(
+    client: &Client,
+    store: Arc<dyn Store>,
+    issue_id: &str,
+    log_completed_requests: bool,
+) -> Result<DownloadOutcome, BugcrawlError> {
     // XXX check for invalid characters
     let url = reqwest::Url::parse(
         format!("https://smartos.org/bugview/fulljson/{}", issue_id).as_str()).unwrap();
     let request = client.get(url).build()?;
-    let response = make_request(&mut bcp, request)?;
+
+    tracing::trace!(method = %request.method(), url = %request.url(), "sending request");
+    let start = std::time::Instant::now();
+    let response = client.execute(request).await?;
+    ::metrics::histogram!("bugcrawl_request_duration_seconds", start.elapsed().as_secs_f64(), "endpoint" => metrics::ENDPOINT_FULLJSON);
+    let status = response.status();
+    ::metrics::counter!("bugcrawl_response_status_total", 1, "endpoint" => metrics::ENDPOINT_FULLJSON, "status" => status.as_u16().to_string());
+    if log_completed_requests {
+        tracing::info!(
+            endpoint = metrics::ENDPOINT_FULLJSON,
+            issue_id,
+            status = status.as_u16(),
+            elapsed_ms = start.elapsed().as_millis() as u64,
+            "request completed"
+        );
+    }
+    if !status.is_success() {
+        return Err(BugcrawlError {
+            message: format!("unexpected response code: {}", status),
+        });
+    }
 
     /*
-     * We could stream this, but we don't want to handle anything that's too
-     * big. TODO-hardening stop accumulating after a given number of bytes
-     * too.
+     * Stream the body chunk-by-chunk through a gzip encoder rather than
+     * buffering it all in memory first, aborting as soon as the
+     * uncompressed size exceeds MAX_ISSUE_LEN regardless of what the
+     * server sends us.
      */
-    let runtime = &mut bcp.tokio_runtime;
-    let content = runtime.block_on(async {
-        response.text().await
-    })?;
+    let byte_stream = response
+        .bytes_stream()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e));
+    let mut reader = StreamReader::new(byte_stream);
+    let mut encoder = GzipEncoder::new(Vec::new());
+    let mut chunk = [0u8; 64 * 1024];
+    let mut total: usize = 0;
 
-    if content.len() > MAX_ISSUE_LEN {
-        return Err(BugcrawlError {
-            message: format!("issue {} was too big ({} bytes, max is {} bytes)",
-                issue_id, content.len(), MAX_ISSUE_LEN)
-        });
+    loop {
+        let n = reader.read(&mut chunk).await.map_err(|e| BugcrawlError {
+            message: format!("error reading response body for {}: {}", issue_id, e),
+        })?;
+        if n == 0 {
+            break;
+        }
+
+        total += n;
+        if total > MAX_ISSUE_LEN {
+            ::metrics::counter!("bugcrawl_oversize_issues_total", 1);
+            return Err(BugcrawlError {
+                message: format!("issue {} was too big (exceeded {} bytes while streaming)",
+                    issue_id, MAX_ISSUE_LEN),
+            });
+        }
+
+        encoder.write_all(&chunk[..n]).await.map_err(|e| BugcrawlError {
+            message: format!("failed to gzip-compress issue {}: {}", issue_id, e),
+        })?;
     }
+    encoder.shutdown().await.map_err(|e| BugcrawlError {
+        message: format!("failed to finish gzip stream for {}: {}", issue_id, e),
+    })?;
+    let compressed = encoder.into_inner();
 
-    let newpath = path_for_issue(&bcp, issue_id, false);
-    let newpath_tmp = path_for_issue(&bcp, issue_id, true);
-    std::fs::write(newpath_tmp.as_path(), content)?;
-    std::fs::rename(newpath_tmp, newpath)?;
-    std::thread::sleep(Duration::from_millis(BUGVIEW_DELAY_GET_ISSUE));
-    Ok(())
+    let bytes_written = compressed.len() as u64;
+    let issue_id = issue_id.to_string();
+    tokio::task::spawn_blocking(move || store.put(&issue_id, &compressed))
+        .await
+        .map_err(|e| BugcrawlError {
+            message: format!("download task panicked: {}", e),
+        })??;
+    ::metrics::counter!("bugcrawl_bytes_written_total", bytes_written);
+    ::metrics::counter!("bugcrawl_issues_downloaded_total", 1);
+    Ok(DownloadOutcome { elapsed: start.elapsed(), bytes_written })
 }
 
-fn make_request(bcp: &mut Bugcrawl, request: reqwest::Request)
+fn make_request(bcp: &mut Bugcrawl, request: reqwest::Request, endpoint: &str)
     -> Result<reqwest::Response, BugcrawlError>
 {
     let client = &bcp.bugview_client;
     let runtime = &mut bcp.tokio_runtime;
 
-    if DBG_REQ {
-        eprintln!("-> {} {}", request.method(), request.url());
-    }
+    tracing::trace!(method = %request.method(), url = %request.url(), "sending request");
+    let start = std::time::Instant::now();
     let response = runtime.block_on(async {
         client.execute(request).await
     })?;
+    ::metrics::histogram!("bugcrawl_request_duration_seconds", start.elapsed().as_secs_f64(), "endpoint" => endpoint.to_string());
     let status = response.status();
-    if DBG_REQ {
-        eprintln!(
-            "<- status {} {}",
-            status.as_str(),
-            status
-                .canonical_reason()
-                .unwrap_or("unknown response code")
+    ::metrics::counter!("bugcrawl_response_status_total", 1, "endpoint" => endpoint.to_string(), "status" => status.as_u16().to_string());
+    if bcp.log_completed_requests {
+        tracing::info!(
+            endpoint,
+            status = status.as_u16(),
+            elapsed_ms = start.elapsed().as_millis() as u64,
+            "request completed"
         );
     }
 
@@ -289,3 +596,31 @@ fn make_request(bcp: &mut Bugcrawl, request: reqwest::Request)
 
     Ok(response)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watermark_excludes_older_issues() {
+        let seen_ids = HashSet::new();
+        let watermark = Watermark { timestamp: "2024-01-15T00:00:00Z", seen_ids: &seen_ids };
+        assert!(watermark.excludes("2024-01-14T23:59:59Z"));
+        assert!(!watermark.excludes("2024-01-15T00:00:00Z"));
+        assert!(!watermark.excludes("2024-01-16T00:00:00Z"));
+    }
+
+    #[test]
+    fn watermark_already_seen_requires_matching_timestamp_and_id() {
+        let mut seen_ids = HashSet::new();
+        seen_ids.insert("ABC-1".to_string());
+        let watermark = Watermark { timestamp: "2024-01-15T00:00:00Z", seen_ids: &seen_ids };
+
+        // same instant, already fetched: skip it
+        assert!(watermark.already_seen("2024-01-15T00:00:00Z", "ABC-1"));
+        // same instant, a different issue that tied with it: not yet seen
+        assert!(!watermark.already_seen("2024-01-15T00:00:00Z", "ABC-2"));
+        // the same issue at a later instant than the watermark: not a dup
+        assert!(!watermark.already_seen("2024-01-16T00:00:00Z", "ABC-1"));
+    }
+}
@@ -0,0 +1,59 @@
+/*!
+ * Structured tracing configuration for crawl diagnostics.
+ *
+ * Installs a `tracing` subscriber whose level, output format (plain text or
+ * JSON), and per-request logging can all be chosen at runtime rather than
+ * baked in at compile time, replacing the old compile-time `DBG_REQ`/
+ * `DBG_ISSUE` flags and scattered `eprintln!` calls.
+ */
+
+use crate::BugcrawlError;
+use tracing_subscriber::EnvFilter;
+
+/** Output format for log lines. */
+pub enum LogFormat {
+    /** human-readable text, one line per event */
+    Plain,
+    /** structured JSON, one object per line */
+    Json,
+}
+
+/**
+ * LoggingParams configures the tracing subscriber installed by `bugcrawl()`.
+ */
+pub struct LoggingParams {
+    /** default log level if the `RUST_LOG` environment variable isn't set */
+    pub level: String,
+    /** output format */
+    pub format: LogFormat,
+    /** whether to log an event for every completed HTTP request */
+    pub log_completed_requests: bool,
+}
+
+impl Default for LoggingParams {
+    fn default() -> LoggingParams {
+        LoggingParams {
+            level: "info".to_string(),
+            format: LogFormat::Plain,
+            log_completed_requests: true,
+        }
+    }
+}
+
+/**
+ * Install the global tracing subscriber described by `params`.  `RUST_LOG`
+ * overrides `params.level` if it's set.
+ */
+pub fn init(params: &LoggingParams) -> Result<(), BugcrawlError> {
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(params.level.clone()));
+
+    let result = match params.format {
+        LogFormat::Plain => tracing_subscriber::fmt().with_env_filter(filter).try_init(),
+        LogFormat::Json => tracing_subscriber::fmt().with_env_filter(filter).json().try_init(),
+    };
+
+    result.map_err(|e| BugcrawlError {
+        message: format!("failed to install tracing subscriber: {}", e),
+    })
+}
@@ -8,16 +8,186 @@ const ARG0_DEFAULT: &str = "bugcrawl";
 const EXIT_FAILURE: i32 = 1;
 
 use bugcrawl::bugcrawl;
+use bugcrawl::run_benchmark;
+use bugcrawl::BenchConfig;
+use bugcrawl::BugcrawlError;
 use bugcrawl::BugcrawlParams;
+use bugcrawl::CrawlMode;
+use bugcrawl::LogFormat;
+use bugcrawl::LoggingParams;
+use bugcrawl::MetricsParams;
+use bugcrawl::StoreBackend;
 
 fn main()
 {
-    let params = BugcrawlParams {
-        filepath: "./bugdb.files",
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let result = if args.first().map(String::as_str) == Some("bench") {
+        run_bench(args.get(1).cloned())
+    } else {
+        run_crawl(&args)
     };
 
-    if let Err(error) = bugcrawl(params) {
+    if let Err(error) = result {
         eprintln!("{}: {}", ARG0_DEFAULT, error);
         std::process::exit(EXIT_FAILURE);
     }
 }
+
+/**
+ * The default crawl behavior.  Every setting defaults to what this binary
+ * has always done (a full crawl into `./bugdb.files` with no metrics) but
+ * can be overridden by an environment variable or, taking precedence, a
+ * same-named `--flag value`:
+ *
+ *   --store filesystem|s3        BUGCRAWL_STORE
+ *   --directory <path>           BUGCRAWL_DIRECTORY      (filesystem store)
+ *   --bucket <name>              BUGCRAWL_BUCKET         (s3 store)
+ *   --prefix <prefix>            BUGCRAWL_PREFIX         (s3 store)
+ *   --concurrency <n>            BUGCRAWL_CONCURRENCY
+ *   --metrics-listen <addr>      BUGCRAWL_METRICS_LISTEN (unset disables metrics)
+ *   --mode full|update           BUGCRAWL_MODE
+ *   --log-level <level>          BUGCRAWL_LOG_LEVEL
+ *   --log-format plain|json      BUGCRAWL_LOG_FORMAT
+ *   --log-completed-requests bool BUGCRAWL_LOG_COMPLETED_REQUESTS
+ */
+fn run_crawl(args: &[String]) -> Result<(), BugcrawlError>
+{
+    let mut store_backend = env_default("BUGCRAWL_STORE", "filesystem");
+    let mut directory = env_default("BUGCRAWL_DIRECTORY", "./bugdb.files");
+    let mut bucket = std::env::var("BUGCRAWL_BUCKET").ok();
+    let mut prefix = env_default("BUGCRAWL_PREFIX", "");
+    let mut concurrency = env_default("BUGCRAWL_CONCURRENCY", "4");
+    let mut metrics_listen = std::env::var("BUGCRAWL_METRICS_LISTEN").ok();
+    let mut mode = env_default("BUGCRAWL_MODE", "full");
+    let mut log_level = env_default("BUGCRAWL_LOG_LEVEL", "info");
+    let mut log_format = env_default("BUGCRAWL_LOG_FORMAT", "plain");
+    let mut log_completed_requests = env_default("BUGCRAWL_LOG_COMPLETED_REQUESTS", "true");
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut next = || {
+            iter.next().cloned().ok_or_else(|| BugcrawlError {
+                message: format!("{} requires a value", arg),
+            })
+        };
+        match arg.as_str() {
+            "--store" => store_backend = next()?,
+            "--directory" => directory = next()?,
+            "--bucket" => bucket = Some(next()?),
+            "--prefix" => prefix = next()?,
+            "--concurrency" => concurrency = next()?,
+            "--metrics-listen" => metrics_listen = Some(next()?),
+            "--mode" => mode = next()?,
+            "--log-level" => log_level = next()?,
+            "--log-format" => log_format = next()?,
+            "--log-completed-requests" => log_completed_requests = next()?,
+            other => {
+                return Err(BugcrawlError {
+                    message: format!("unrecognized argument: {}", other),
+                })
+            }
+        }
+    }
+
+    let store = if store_backend == "s3" {
+        StoreBackend::S3 {
+            bucket: bucket.as_deref().ok_or_else(|| BugcrawlError {
+                message: "--bucket (or BUGCRAWL_BUCKET) is required for the s3 store".to_string(),
+            })?,
+            prefix: &prefix,
+        }
+    } else {
+        StoreBackend::Filesystem { directory: &directory }
+    };
+
+    let concurrency: usize = concurrency.parse().map_err(|_| BugcrawlError {
+        message: format!("invalid --concurrency value: {}", concurrency),
+    })?;
+
+    let metrics = metrics_listen
+        .map(|addr| {
+            addr.parse().map(|listen_address| MetricsParams { listen_address }).map_err(|_| {
+                BugcrawlError { message: format!("invalid --metrics-listen address: {}", addr) }
+            })
+        })
+        .transpose()?;
+
+    let mode = match mode.as_str() {
+        "full" => CrawlMode::Full,
+        "update" => CrawlMode::Update,
+        other => {
+            return Err(BugcrawlError {
+                message: format!("invalid --mode value: {} (expected \"full\" or \"update\")", other),
+            })
+        }
+    };
+
+    let format = match log_format.as_str() {
+        "plain" => LogFormat::Plain,
+        "json" => LogFormat::Json,
+        other => {
+            return Err(BugcrawlError {
+                message: format!("invalid --log-format value: {} (expected \"plain\" or \"json\")", other),
+            })
+        }
+    };
+
+    let log_completed_requests = parse_bool(&log_completed_requests)?;
+
+    let params = BugcrawlParams {
+        store,
+        concurrency,
+        metrics,
+        logging: LoggingParams {
+            level: log_level,
+            format,
+            log_completed_requests,
+        },
+        mode,
+    };
+
+    bugcrawl(params)
+}
+
+/** Read `var` from the environment, falling back to `default` if it's unset. */
+fn env_default(var: &str, default: &str) -> String {
+    std::env::var(var).unwrap_or_else(|_| default.to_string())
+}
+
+/** Parse a `true`/`false` flag value, case-insensitively. */
+fn parse_bool(value: &str) -> Result<bool, BugcrawlError> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        other => Err(BugcrawlError {
+            message: format!("invalid boolean value: {} (expected \"true\" or \"false\")", other),
+        }),
+    }
+}
+
+/**
+ * The "bench" subcommand: load the workload file named by `config_path` and
+ * run `bugcrawl::run_benchmark` against it, printing the resulting report as
+ * JSON to stdout.
+ */
+fn run_bench(config_path: Option<String>) -> Result<(), BugcrawlError>
+{
+    let config_path = config_path.ok_or_else(|| BugcrawlError {
+        message: format!("usage: {} bench <workload-file.json>", ARG0_DEFAULT),
+    })?;
+
+    let contents = std::fs::read_to_string(&config_path).map_err(|e| BugcrawlError {
+        message: format!("failed to read workload file {}: {}", config_path, e),
+    })?;
+    let config: BenchConfig = serde_json::from_str(&contents).map_err(|e| BugcrawlError {
+        message: format!("failed to parse workload file {}: {}", config_path, e),
+    })?;
+
+    let report = run_benchmark(&config)?;
+    let report_json = serde_json::to_string_pretty(&report).map_err(|e| BugcrawlError {
+        message: format!("failed to serialize benchmark report: {}", e),
+    })?;
+    println!("{}", report_json);
+    Ok(())
+}
@@ -0,0 +1,38 @@
+/*!
+ * Optional Prometheus metrics for crawl observability.
+ *
+ * When enabled via `BugcrawlParams`, this stands up a Prometheus exporter
+ * endpoint and records counters/histograms as the crawl runs: request
+ * counts and latencies per endpoint, issues listed/downloaded/skipped, and
+ * bytes written.
+ */
+
+use crate::BugcrawlError;
+use metrics_exporter_prometheus::PrometheusBuilder;
+use std::net::SocketAddr;
+
+/** names of the endpoints we record per-request metrics for */
+pub const ENDPOINT_LIST: &str = "list";
+pub const ENDPOINT_FULLJSON: &str = "fulljson";
+
+/**
+ * MetricsParams describes the optional metrics exporter a caller may enable.
+ */
+pub struct MetricsParams {
+    /** address the Prometheus exporter's HTTP endpoint listens on */
+    pub listen_address: SocketAddr,
+}
+
+/**
+ * Install the Prometheus recorder and start its HTTP exporter on
+ * `params.listen_address`.  Must be called with a tokio runtime entered,
+ * since the exporter spawns a task to serve scrapes.
+ */
+pub fn install(params: &MetricsParams) -> Result<(), BugcrawlError> {
+    PrometheusBuilder::new()
+        .with_http_listener(params.listen_address)
+        .install()
+        .map_err(|e| BugcrawlError {
+            message: format!("failed to install metrics exporter: {}", e),
+        })
+}
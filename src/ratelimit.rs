@@ -0,0 +1,65 @@
+/*!
+ * A minimal shared rate limiter for politeness delays between requests that
+ * may now be issued from multiple concurrent tasks.
+ */
+
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/**
+ * RateLimiter enforces a minimum interval between successive `acquire()`
+ * calls across every caller that shares it, regardless of how many tasks are
+ * calling it concurrently.  This replaces a fixed per-request
+ * `std::thread::sleep` with something that still throttles the aggregate
+ * request rate once requests are issued from a pool of tasks.
+ */
+pub struct RateLimiter {
+    min_interval: Duration,
+    last: Mutex<Option<Instant>>,
+}
+
+impl RateLimiter {
+    /** Construct a limiter that allows at most one `acquire()` per `min_interval`. */
+    pub fn new(min_interval: Duration) -> RateLimiter {
+        RateLimiter {
+            min_interval,
+            last: Mutex::new(None),
+        }
+    }
+
+    /** Wait until at least `min_interval` has elapsed since the last `acquire()`. */
+    pub async fn acquire(&self) {
+        let mut last = self.last.lock().await;
+        let now = Instant::now();
+        if let Some(prev) = *last {
+            let elapsed = now.saturating_duration_since(prev);
+            if elapsed < self.min_interval {
+                tokio::time::sleep(self.min_interval - elapsed).await;
+            }
+        }
+        *last = Some(Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn enforces_minimum_interval_between_acquires() {
+        let limiter = RateLimiter::new(Duration::from_millis(50));
+        let start = Instant::now();
+        limiter.acquire().await;
+        limiter.acquire().await;
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    }
+
+    #[tokio::test]
+    async fn first_acquire_does_not_wait() {
+        let limiter = RateLimiter::new(Duration::from_secs(60));
+        let start = Instant::now();
+        limiter.acquire().await;
+        assert!(start.elapsed() < Duration::from_millis(100));
+    }
+}
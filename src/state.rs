@@ -0,0 +1,79 @@
+/*!
+ * Persisted crawl state, used to drive incremental "update" crawls.
+ *
+ * `CrawlState` holds the high-watermark `updated` timestamp seen across the
+ * last completed crawl.  It's stored through the same `Store` used for issue
+ * content, so it rides along wherever issues are kept.
+ */
+
+use crate::store::Store;
+use crate::BugcrawlError;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::Deserialize;
+use serde::Serialize;
+use std::io::Read;
+use std::io::Write;
+
+/** identifier under which crawl state is stored, alongside downloaded issues */
+pub(crate) const STATE_KEY: &str = ".bugcrawl-state";
+
+/**
+ * CrawlState is the persisted bookkeeping for incremental crawls.
+ */
+#[derive(Serialize, Deserialize, Default)]
+pub struct CrawlState {
+    /** the most recent issue `updated` timestamp seen across a completed crawl */
+    pub high_watermark: Option<String>,
+    /**
+     * ids of every issue already fetched whose `updated` timestamp is
+     * exactly `high_watermark`.  Since bugview doesn't order same-instant
+     * ties, a bare timestamp cursor can't tell "already fetched at this
+     * instant" apart from "updated again at this instant" — this set is
+     * what lets `Update` crawls tell those apart instead of permanently
+     * skipping anything sharing the watermark's timestamp.
+     */
+    pub high_watermark_ids: Vec<String>,
+}
+
+impl CrawlState {
+    /** Load the persisted crawl state, or the default if none has been written yet. */
+    pub fn load(store: &dyn Store) -> Result<CrawlState, BugcrawlError> {
+        match store.get(STATE_KEY)? {
+            Some(compressed) => {
+                let mut bytes = Vec::new();
+                GzDecoder::new(compressed.as_slice())
+                    .read_to_end(&mut bytes)
+                    .map_err(|e| BugcrawlError {
+                        message: format!("failed to decompress crawl state: {}", e),
+                    })?;
+                serde_json::from_slice(&bytes).map_err(|e| BugcrawlError {
+                    message: format!("failed to parse crawl state: {}", e),
+                })
+            }
+            None => Ok(CrawlState::default()),
+        }
+    }
+
+    /**
+     * Persist this crawl state, replacing any previous copy.  The state is
+     * gzip-compressed like everything else under `Store`, even though it's
+     * small, so that every object a store holds follows the same format.
+     */
+    pub fn save(&self, store: &dyn Store) -> Result<(), BugcrawlError> {
+        let bytes = serde_json::to_vec_pretty(self).map_err(|e| BugcrawlError {
+            message: format!("failed to serialize crawl state: {}", e),
+        })?;
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&bytes).map_err(|e| BugcrawlError {
+            message: format!("failed to compress crawl state: {}", e),
+        })?;
+        let compressed = encoder.finish().map_err(|e| BugcrawlError {
+            message: format!("failed to compress crawl state: {}", e),
+        })?;
+
+        store.put(STATE_KEY, &compressed)
+    }
+}
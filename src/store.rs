@@ -0,0 +1,249 @@
+/*!
+ * Storage backends for downloaded issue content.
+ *
+ * `Bugcrawl` writes each downloaded issue through a `Store` rather than
+ * directly to the filesystem so that a crawl can be pointed at local disk
+ * (`FilesystemStore`) or directly at an object store (`S3Store`) without
+ * changing any of the crawl logic in `lib.rs`.
+ */
+
+use crate::error::BugcrawlError;
+use crate::state::STATE_KEY;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::runtime::Handle;
+
+/**
+ * Store is implemented by each storage backend we support for writing
+ * downloaded issue content.  Implementations must make `put()` atomic: a
+ * caller that observes a `put()` failure, or that concurrently calls
+ * `exists()`/`list_existing()`, must never see a partially-written issue.
+ */
+pub trait Store: Send + Sync {
+    /** Write `contents` for `issue_id`, replacing any existing copy. */
+    fn put(&self, issue_id: &str, contents: &[u8]) -> Result<(), BugcrawlError>;
+
+    /** Return whether we already have a copy of `issue_id`. */
+    fn exists(&self, issue_id: &str) -> Result<bool, BugcrawlError>;
+
+    /** Read back the contents previously `put()` for `issue_id`, if any. */
+    fn get(&self, issue_id: &str) -> Result<Option<Vec<u8>>, BugcrawlError>;
+
+    /**
+     * List the identifiers of all issues already present in the store.
+     * Excludes reserved keys (e.g. the crawl state written by
+     * `CrawlState::save`) that share the same namespace but aren't issues.
+     */
+    fn list_existing(&self) -> Result<Vec<String>, BugcrawlError>;
+}
+
+/**
+ * Stores issues as flat files under a local directory.  This is today's
+ * behavior: issues are written to a temporary file and renamed into place so
+ * that a reader never observes a partially-written issue.
+ */
+pub struct FilesystemStore {
+    directory: PathBuf,
+}
+
+impl FilesystemStore {
+    /** Create (if necessary) and return a store rooted at `directory`. */
+    pub fn new(directory: PathBuf) -> Result<FilesystemStore, BugcrawlError> {
+        fs::create_dir_all(&directory)?;
+        Ok(FilesystemStore { directory })
+    }
+
+    /**
+     * Given an issue identifier, return the local filesystem path where we
+     * will store the issue.  If `tmp` is set, return a temporary file name to
+     * be used for this issue's content.
+     */
+    fn path_for(&self, issue_id: &str, tmp: bool) -> PathBuf {
+        let mut path = self.directory.clone();
+        // XXX sanity-check for invalid characters
+        path.push(format!("{}.json.gz{}", issue_id, if tmp { ".tmp" } else { "" }));
+        path
+    }
+}
+
+impl Store for FilesystemStore {
+    fn put(&self, issue_id: &str, contents: &[u8]) -> Result<(), BugcrawlError> {
+        let newpath = self.path_for(issue_id, false);
+        let newpath_tmp = self.path_for(issue_id, true);
+        fs::write(&newpath_tmp, contents)?;
+        fs::rename(newpath_tmp, newpath)?;
+        Ok(())
+    }
+
+    fn exists(&self, issue_id: &str) -> Result<bool, BugcrawlError> {
+        match fs::metadata(self.path_for(issue_id, false)) {
+            Ok(_) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn get(&self, issue_id: &str) -> Result<Option<Vec<u8>>, BugcrawlError> {
+        match fs::read(self.path_for(issue_id, false)) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn list_existing(&self) -> Result<Vec<String>, BugcrawlError> {
+        let mut ids = Vec::new();
+        for entry in fs::read_dir(&self.directory)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            if let Some(id) = name.to_str().and_then(|n| n.strip_suffix(".json.gz")) {
+                if id != STATE_KEY {
+                    ids.push(id.to_string());
+                }
+            }
+        }
+        Ok(ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /** A directory under the system temp dir, unique to this test process and name. */
+    fn temp_store_dir(label: &str) -> PathBuf {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        std::env::temp_dir().join(format!("bugcrawl-store-test-{}-{}-{}", label, std::process::id(), nanos))
+    }
+
+    #[test]
+    fn path_for_appends_gz_and_tmp_suffixes() {
+        let store = FilesystemStore::new(temp_store_dir("path-for")).unwrap();
+        assert_eq!(store.path_for("ABC-1", false).file_name().unwrap(), "ABC-1.json.gz");
+        assert_eq!(store.path_for("ABC-1", true).file_name().unwrap(), "ABC-1.json.gz.tmp");
+    }
+
+    #[test]
+    fn list_existing_excludes_the_state_key() {
+        let store = FilesystemStore::new(temp_store_dir("list-existing")).unwrap();
+        store.put("ABC-1", b"issue contents").unwrap();
+        store.put(STATE_KEY, b"crawl state").unwrap();
+
+        let mut ids = store.list_existing().unwrap();
+        ids.sort();
+        assert_eq!(ids, vec!["ABC-1".to_string()]);
+    }
+
+    #[test]
+    fn exists_reflects_whether_put_was_called() {
+        let store = FilesystemStore::new(temp_store_dir("exists")).unwrap();
+        assert!(!store.exists("ABC-1").unwrap());
+        store.put("ABC-1", b"issue contents").unwrap();
+        assert!(store.exists("ABC-1").unwrap());
+    }
+}
+
+/**
+ * Stores issues as objects in an S3-compatible bucket, keyed by
+ * `{prefix}/{issue_id}.json.gz`.  Each `put()` is a single atomic PUT, which
+ * is the closest S3 equivalent of the filesystem store's temp-then-rename.
+ */
+pub struct S3Store {
+    client: Arc<dyn object_store::ObjectStore>,
+    prefix: String,
+    runtime: Handle,
+}
+
+impl S3Store {
+    /** Create a store backed by `bucket`, keying objects under `prefix`. */
+    pub fn new(bucket: &str, prefix: &str, runtime: Handle) -> Result<S3Store, BugcrawlError> {
+        let client = object_store::aws::AmazonS3Builder::from_env()
+            .with_bucket_name(bucket)
+            .build()
+            .map_err(|e| BugcrawlError {
+                message: format!("failed to configure S3 store for bucket {}: {}", bucket, e),
+            })?;
+        Ok(S3Store {
+            client: Arc::new(client),
+            prefix: prefix.to_string(),
+            runtime,
+        })
+    }
+
+    fn object_path(&self, issue_id: &str) -> object_store::path::Path {
+        object_store::path::Path::from(format!("{}/{}.json.gz", self.prefix, issue_id))
+    }
+}
+
+impl Store for S3Store {
+    fn put(&self, issue_id: &str, contents: &[u8]) -> Result<(), BugcrawlError> {
+        let client = Arc::clone(&self.client);
+        let path = self.object_path(issue_id);
+        let bytes = bytes::Bytes::copy_from_slice(contents);
+        self.runtime
+            .block_on(async move { client.put(&path, bytes.into()).await })
+            .map_err(|e| BugcrawlError {
+                message: format!("S3 put of {} failed: {}", issue_id, e),
+            })?;
+        Ok(())
+    }
+
+    fn exists(&self, issue_id: &str) -> Result<bool, BugcrawlError> {
+        let client = Arc::clone(&self.client);
+        let path = self.object_path(issue_id);
+        match self.runtime.block_on(async move { client.head(&path).await }) {
+            Ok(_) => Ok(true),
+            Err(object_store::Error::NotFound { .. }) => Ok(false),
+            Err(e) => Err(BugcrawlError {
+                message: format!("S3 head of {} failed: {}", issue_id, e),
+            }),
+        }
+    }
+
+    fn get(&self, issue_id: &str) -> Result<Option<Vec<u8>>, BugcrawlError> {
+        let client = Arc::clone(&self.client);
+        let path = self.object_path(issue_id);
+        let result = self.runtime.block_on(async move {
+            match client.get(&path).await {
+                Ok(result) => Ok(Some(result.bytes().await?)),
+                Err(object_store::Error::NotFound { .. }) => Ok(None),
+                Err(e) => Err(e),
+            }
+        });
+
+        match result {
+            Ok(bytes) => Ok(bytes.map(|b| b.to_vec())),
+            Err(e) => Err(BugcrawlError {
+                message: format!("S3 get of {} failed: {}", issue_id, e),
+            }),
+        }
+    }
+
+    fn list_existing(&self) -> Result<Vec<String>, BugcrawlError> {
+        use futures::TryStreamExt;
+
+        let client = Arc::clone(&self.client);
+        let prefix = object_store::path::Path::from(self.prefix.clone());
+        let entries = self
+            .runtime
+            .block_on(async move { client.list(Some(&prefix)).try_collect::<Vec<_>>().await })
+            .map_err(|e| BugcrawlError {
+                message: format!("S3 list under {} failed: {}", self.prefix, e),
+            })?;
+
+        Ok(entries
+            .into_iter()
+            .filter_map(|meta| {
+                meta.location
+                    .filename()
+                    .and_then(|name| name.strip_suffix(".json.gz"))
+                    .map(|id| id.to_string())
+            })
+            .filter(|id| id != STATE_KEY)
+            .collect())
+    }
+}